@@ -7,11 +7,31 @@ use crate::{rumor::{RumorKey,
             server::{timing::Timing,
                      Server}};
 use chrono::offset::Utc;
-use std::{thread,
+use lazy_static::lazy_static;
+use std::{sync::RwLock,
+          thread,
           time::Duration};
 
 const LOOP_DELAY_MS: u64 = 500;
 
+lazy_static! {
+    // `butterfly` doesn't know anything about event streams, NATS, or
+    // the Supervisor's metadata; it just reports member transitions to
+    // whoever has registered interest via `register_member_event_handlers`.
+    static ref MEMBER_CONFIRMED_HANDLER: RwLock<Option<fn(&str)>> = RwLock::new(None);
+    static ref MEMBER_DEPARTED_HANDLER: RwLock<Option<fn(&str)>> = RwLock::new(None);
+}
+
+/// Registers the functions that get called whenever `Expire::run`
+/// observes a member transition to `Confirmed` or `Departed`. The
+/// Supervisor wires this up to its event-stream publishers at startup.
+pub fn register_member_event_handlers(on_confirmed: fn(&str), on_departed: fn(&str)) {
+    *MEMBER_CONFIRMED_HANDLER.write()
+                             .expect("member event handler lock poisoned") = Some(on_confirmed);
+    *MEMBER_DEPARTED_HANDLER.write()
+                            .expect("member event handler lock poisoned") = Some(on_departed);
+}
+
 pub struct Expire {
     pub server: Server,
     pub timing: Timing,
@@ -31,6 +51,11 @@ impl Expire {
                 self.server
                     .rumor_heat
                     .start_hot_rumor(RumorKey::new(RumorType::Member, &id, ""));
+                if let Some(handler) = *MEMBER_CONFIRMED_HANDLER.read()
+                                                                .expect("member event handler lock poisoned")
+                {
+                    handler(&id);
+                }
             }
 
             let newly_departed_members =
@@ -43,6 +68,11 @@ impl Expire {
                 self.server
                     .rumor_heat
                     .start_hot_rumor(RumorKey::new(RumorType::Member, &id, ""));
+                if let Some(handler) = *MEMBER_DEPARTED_HANDLER.read()
+                                                               .expect("member event handler lock poisoned")
+                {
+                    handler(&id);
+                }
             }
 
             // JB TODO: How does this work for members, since members aren't /quite/
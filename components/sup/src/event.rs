@@ -23,17 +23,31 @@
 //!
 //! All events are published under the "habitat" subject.
 //!
+//! `stats` exposes a snapshot of the stream's health (queued, published,
+//! acked, dropped, and connection state) for the Supervisor's HTTP
+//! gateway to render.
+//!
 //! [1]:https://github.com/nats-io/nats-streaming-server
 
+mod buffer;
+mod metrics;
 mod types;
 
-use self::types::{EventMessage,
-                  ServiceStartedEvent,
-                  ServiceStoppedEvent};
+use self::{buffer::{EventBufferReceiver,
+                    EventBufferSender,
+                    SendOutcome},
+           metrics::EventStreamStats,
+           types::{EventMessage,
+                   MemberConfirmedEvent,
+                   MemberDepartedEvent,
+                   ServiceStartedEvent,
+                   ServiceStoppedEvent}};
 use crate::{error::Result,
             manager::service::Service};
-use futures::{sync::{mpsc as futures_mpsc,
-                     mpsc::UnboundedSender},
+use chrono::Utc;
+use futures::{future::{self,
+                       Loop},
+              sync::oneshot,
               Future,
               Stream};
 use nitox::{commands::ConnectCommand,
@@ -41,13 +55,87 @@ use nitox::{commands::ConnectCommand,
                         error::NatsStreamingError},
             NatsClient,
             NatsClientOptions};
+use rand::Rng;
 use state::Container;
-use std::{sync::{mpsc as std_mpsc,
+use std::{cell::{Cell,
+                 RefCell},
+          collections::HashMap,
+          rc::Rc,
+          sync::{atomic::{AtomicU64,
+                          Ordering},
+                 mpsc as std_mpsc,
+                 Arc,
                  Once},
-          thread};
+          thread,
+          time::Duration};
 use tokio::{executor,
+            prelude::FutureExt,
             runtime::current_thread::Runtime as ThreadRuntime};
 
+pub use self::{buffer::OverflowPolicy,
+               metrics::EventStreamStatsSnapshot};
+
+/// Default depth of the bounded event buffer; see
+/// `EventConnectionInfo::buffer_capacity`.
+const DEFAULT_BUFFER_CAPACITY: usize = 1024;
+/// Default ack-wait timeout for a publish, in either `DeliveryMode`.
+const DEFAULT_ACK_WAIT: Duration = Duration::from_secs(5);
+/// Default total number of publish attempts (the first one included) a
+/// `DeliveryMode::Durable` event gets before we give up on it.
+const DEFAULT_MAX_PUBLISH_ATTEMPTS: u32 = 5;
+
+/// How hard we try to get an event delivered once it leaves the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Publish once; if it isn't acked within the connection's timeout,
+    /// log it and move on.
+    FireAndForget,
+    /// Treat a missing or negative ack as a transient failure and
+    /// re-publish the event (up to a bounded number of attempts)
+    /// instead of dropping it.
+    Durable,
+}
+
+/// A rendered event together with the monotonically increasing sequence
+/// number it was assigned when it was published. That sequence number
+/// doubles as the event's stable message id (see `event::types`), and
+/// lets the drain loop track in-flight, not-yet-acked sends across
+/// retries and reconnects.
+struct QueuedEvent {
+    id:    u64,
+    bytes: Vec<u8>,
+}
+
+/// Source of the sequence number assigned to each outgoing event.
+static NEXT_MESSAGE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Fires (at most once per connection) when `spawn_publish` gives up on
+/// an event. A successful initial `connect()` only tells us the link
+/// was up *then*; this is how a publish that later finds it dead gets
+/// word back to `run_event_thread`, so a live disconnect re-enters the
+/// backoff/reconnect path instead of retrying forever against a
+/// connection that's already gone.
+type DisconnectSignal = Rc<RefCell<Option<oneshot::Sender<()>>>>;
+
+/// Events handed off to `spawn_publish` that haven't yet been acked or
+/// given up on for good, keyed by the sequence number they were queued
+/// under. When a connection dies out from under them, whatever's still
+/// in here is migrated back onto the buffer (see `requeue_in_flight`) so
+/// the next connection attempt redelivers it, rather than letting each
+/// event's own retry loop quietly run out the clock against a dead
+/// client.
+type InFlight = Rc<RefCell<HashMap<u64, QueuedEvent>>>;
+
+/// Starting point for the reconnect backoff; doubled after every failed
+/// connection attempt up to `MAX_RECONNECT_BACKOFF`.
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound on how long we'll wait between reconnect attempts.
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+/// Extra random delay mixed into every backoff so that a fleet of
+/// Supervisors that all lost their connection at the same time don't all
+/// hammer A2 with reconnects in lock-step.
+const RECONNECT_JITTER_MS: u64 = 250;
+
 static INIT: Once = Once::new();
 lazy_static! {
     // TODO (CM): When const fn support lands in stable, we can ditch
@@ -57,8 +145,18 @@ lazy_static! {
     static ref EVENT_STREAM: Container = Container::new();
     /// Core information that is shared between all events.
     static ref EVENT_CORE: Container = Container::new();
+    /// Counters and gauges describing the health of the event stream.
+    /// Unlike `EVENT_STREAM` and `EVENT_CORE`, this exists unconditionally
+    /// (rather than behind `INIT`) so the HTTP gateway can render it even
+    /// before `init_stream` has been called.
+    static ref EVENT_STREAM_STATS: EventStreamStats = EventStreamStats::default();
 }
 
+/// A snapshot of the event stream's health, for the Supervisor's HTTP
+/// gateway to render (e.g. in Prometheus text-exposition format via
+/// `EventStreamStatsSnapshot::to_prometheus`).
+pub fn stats() -> EventStreamStatsSnapshot { EVENT_STREAM_STATS.snapshot() }
+
 /// Starts a new thread for sending events to a NATS Streaming
 /// server. Stashes the handle to the stream, as well as the core
 /// event information that will be a part of all events, in a global
@@ -68,6 +166,11 @@ pub fn init_stream(conn_info: EventConnectionInfo, event_core: EventCore) {
             let event_stream = init_nats_stream(conn_info).expect("Could not start NATS thread");
             EVENT_STREAM.set(event_stream);
             EVENT_CORE.set(event_core);
+            // `butterfly` has no concept of an "event stream"; it just
+            // calls back into whatever's registered here whenever a
+            // member's health state changes.
+            habitat_butterfly::server::expire::register_member_event_handlers(member_confirmed,
+                                                                               member_departed);
         });
 }
 
@@ -80,6 +183,22 @@ pub struct EventConnectionInfo {
     pub verbose:     bool,
     pub cluster_uri: String,
     pub cluster_id:  String,
+    /// How many rendered events the in-memory buffer will hold before
+    /// `overflow_policy` kicks in.
+    pub buffer_capacity: usize,
+    /// What to do once the buffer is full.
+    pub overflow_policy: OverflowPolicy,
+    /// Fire-and-forget vs. acked/durable delivery of published events.
+    pub delivery_mode: DeliveryMode,
+    /// How long to wait for an ack before treating a publish as failed.
+    /// Applies to every publish regardless of `delivery_mode`: in
+    /// `FireAndForget` mode it's what decides when that one and only
+    /// attempt gives up.
+    pub ack_wait: Duration,
+    /// How many publish attempts in total (the first one included) a
+    /// `Durable` event gets before it's given up on. `FireAndForget` never
+    /// retries, so this is unused in that mode.
+    pub max_publish_attempts: u32,
 }
 
 /// A collection of data that will be present in all events. Rather
@@ -99,7 +218,7 @@ pub struct EventCore {
 
 /// Send an event for the start of a Service.
 pub fn service_started(service: &Service) {
-    if stream_initialized() {
+    if should_publish() {
         publish(ServiceStartedEvent { service_metadata:    Some(service.to_service_metadata()),
                                       supervisor_metadata: None, });
     }
@@ -107,18 +226,42 @@ pub fn service_started(service: &Service) {
 
 /// Send an event for the stop of a Service.
 pub fn service_stopped(service: &Service) {
-    if stream_initialized() {
+    if should_publish() {
         publish(ServiceStoppedEvent { service_metadata:    Some(service.to_service_metadata()),
                                       supervisor_metadata: None, });
     }
 }
 
+/// Send an event for a member transitioning from `Suspect` to
+/// `Confirmed`.
+pub fn member_confirmed(member_id: &str) {
+    if should_publish() {
+        publish(MemberConfirmedEvent { member_id:           member_id.to_string(),
+                                       supervisor_metadata: None, });
+    }
+}
+
+/// Send an event for a member transitioning to `Departed`.
+pub fn member_departed(member_id: &str) {
+    if should_publish() {
+        publish(MemberDepartedEvent { member_id:           member_id.to_string(),
+                                      supervisor_metadata: None, });
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////
 
-/// Internal helper function to know whether or not to go to the trouble of
-/// creating event structures. If the event stream hasn't been
-/// initialized, then we shouldn't need to do anything.
-fn stream_initialized() -> bool { EVENT_STREAM.try_get::<EventStream>().is_some() }
+/// Internal helper function to know whether or not to go to the trouble
+/// of creating event structures. If the event stream hasn't been
+/// initialized, or the buffer is already full and would just drop the
+/// event we're about to build anyway, then we shouldn't need to do
+/// anything.
+fn should_publish() -> bool {
+    match EVENT_STREAM.try_get::<EventStream>() {
+        Some(stream) => !stream.would_drop(),
+        None => false,
+    }
+}
 
 /// Publish an event. This is the main interface that client code will
 /// use.
@@ -126,29 +269,54 @@ fn stream_initialized() -> bool { EVENT_STREAM.try_get::<EventStream>().is_some(
 /// If `init_stream` has not been called already, this function will
 /// be a no-op.
 fn publish(mut event: impl EventMessage) {
-    // TODO: incorporate the current timestamp into the rendered event
-    // (which will require tweaks to the rendering logic, but we know
-    // that'll need to be updated anyway).
     if let Some(e) = EVENT_STREAM.try_get::<EventStream>() {
         event.supervisor_metadata(EVENT_CORE.get::<EventCore>().to_supervisor_metadata());
-        if let Ok(bytes) = event.to_bytes() {
-            e.send(bytes);
+        let id = NEXT_MESSAGE_ID.fetch_add(1, Ordering::SeqCst);
+        if let Ok(bytes) = event.to_bytes(&id.to_string(), Utc::now()) {
+            match e.send(QueuedEvent { id, bytes }) {
+                SendOutcome::Enqueued => {
+                    EVENT_STREAM_STATS.record_enqueued();
+                }
+                SendOutcome::Blocked => {
+                    warn!("Event buffer was full; blocked until event {} could be queued", id);
+                    EVENT_STREAM_STATS.record_enqueued();
+                }
+                SendOutcome::DroppedNewest => {
+                    warn!("Event buffer was full; dropped event {} that was just generated", id);
+                    EVENT_STREAM_STATS.record_dropped();
+                }
+                SendOutcome::DroppedOldest => {
+                    warn!("Event buffer was full; dropped the oldest queued event to make room for event {}",
+                          id);
+                    // `id` itself was enqueued; it's some older, already-
+                    // enqueued event that got evicted to make room for it.
+                    EVENT_STREAM_STATS.record_enqueued();
+                    EVENT_STREAM_STATS.record_dropped();
+                }
+            }
         }
     }
 }
 
 /// A lightweight handle for the event stream. All events get to the
 /// event stream through this.
-struct EventStream(UnboundedSender<Vec<u8>>);
+struct EventStream(EventBufferSender<QueuedEvent>);
 
 impl EventStream {
-    /// Queues an event to be sent out.
-    fn send(&self, event: Vec<u8>) {
-        trace!("About to queue an event: {:?}", event);
-        if let Err(e) = self.0.unbounded_send(event) {
-            error!("Failed to queue event: {:?}", e);
-        }
+    /// Queues an event to be sent out, applying the buffer's overflow
+    /// policy if it's already full.
+    ///
+    /// Queue depth is kept live by the buffer's depth hook (wired up in
+    /// `init_nats_stream`) on every enqueue, dequeue, and requeue, so
+    /// there's nothing left to report here.
+    fn send(&self, event: QueuedEvent) -> SendOutcome {
+        trace!("About to queue event {}", event.id);
+        self.0.send(event)
     }
+
+    /// Cheaply reports whether `send` would drop the event outright
+    /// right now.
+    fn would_drop(&self) -> bool { self.0.would_drop() }
 }
 
 ////////////////////////////////////////////////////////////////////////
@@ -163,16 +331,23 @@ const HABITAT_SUBJECT: &str = "habitat";
 // prototyping, though.
 impl Default for EventConnectionInfo {
     fn default() -> Self {
-        EventConnectionInfo { name:        String::from("habitat"),
-                              verbose:     true,
-                              cluster_uri: String::from("127.0.0.1:4223"),
-                              cluster_id:  String::from("test-cluster"), }
+        EventConnectionInfo { name:                 String::from("habitat"),
+                              verbose:              true,
+                              cluster_uri:          String::from("127.0.0.1:4223"),
+                              cluster_id:           String::from("test-cluster"),
+                              buffer_capacity:      DEFAULT_BUFFER_CAPACITY,
+                              overflow_policy:      OverflowPolicy::DropOldest,
+                              delivery_mode:        DeliveryMode::FireAndForget,
+                              ack_wait:             DEFAULT_ACK_WAIT,
+                              max_publish_attempts: DEFAULT_MAX_PUBLISH_ATTEMPTS, }
     }
 }
 
 fn init_nats_stream(conn_info: EventConnectionInfo) -> Result<EventStream> {
-    // TODO (CM): Investigate back-pressure scenarios
-    let (event_tx, event_rx) = futures_mpsc::unbounded();
+    let (event_tx, event_rx) =
+        buffer::bounded_with_depth_hook(conn_info.buffer_capacity,
+                                         conn_info.overflow_policy,
+                                         |depth| EVENT_STREAM_STATS.set_queue_depth(depth));
     let (sync_tx, sync_rx) = std_mpsc::sync_channel(0); // rendezvous channel
 
     // TODO (CM): We could theoretically create this future and spawn
@@ -181,52 +356,251 @@ fn init_nats_stream(conn_info: EventConnectionInfo) -> Result<EventStream> {
 
     thread::Builder::new().name("events".to_string())
                           .spawn(move || {
-                              let EventConnectionInfo { name,
-                                                        verbose,
-                                                        cluster_uri,
-                                                        cluster_id, } = conn_info;
+                              // We only need to rendezvous on the thread
+                              // itself being up; whether or not A2 is
+                              // actually reachable is handled below by
+                              // the reconnect loop, so `init_stream`
+                              // doesn't block forever (or panic) just
+                              // because the NATS Streaming server is
+                              // down when the Supervisor starts.
+                              sync_tx.send(()).expect("Couldn't synchronize!");
+                              run_event_thread(conn_info, event_rx);
+                          })
+                          .expect("Couldn't start events thread!");
+
+    sync_rx.recv()?; // TODO (CM): nicer error message
+    Ok(EventStream(event_tx))
+}
+
+/// Connects to the configured NATS Streaming server and drains
+/// `event_rx` into it until every sender of the buffer has been dropped.
+/// If the connection can't be established,
+/// or is lost while draining, we back off exponentially (with jitter)
+/// and try again.
+///
+/// Crucially, `event_rx` is owned by this function for its entire
+/// lifetime rather than being moved into a one-shot `for_each`; each
+/// connection attempt only ever borrows it (via `by_ref`), so events
+/// queued up while we're disconnected are simply delivered once the
+/// link comes back, instead of being lost along with a dead future.
+///
+/// The drain loop by itself would only ever notice a connection drop
+/// the next time it had something new to publish (or never, if
+/// publishes fail silently). We race it against a `DisconnectSignal`
+/// that `spawn_publish` fires once it's given up on an event, so a link
+/// that dies *after* we've connected is detected just as reliably as one
+/// that was never up in the first place.
+fn run_event_thread(conn_info: EventConnectionInfo, mut event_rx: EventBufferReceiver<QueuedEvent>) {
+    let EventConnectionInfo { name,
+                              verbose,
+                              cluster_uri,
+                              cluster_id,
+                              delivery_mode,
+                              ack_wait,
+                              max_publish_attempts,
+                              .. } = conn_info;
+
+    let mut runtime = ThreadRuntime::new().expect("Couldn't create event stream runtime!");
+    let mut backoff = INITIAL_RECONNECT_BACKOFF;
+
+    loop {
+        let in_flight: InFlight = Rc::new(RefCell::new(HashMap::new()));
 
-                              let cc = ConnectCommand::builder()
+        let cc = ConnectCommand::builder()
                 // .user(Some("nats".to_string()))
                 // .pass(Some("S3Cr3TP@5w0rD".to_string()))
-                .name(Some(name))
+                .name(Some(name.clone()))
                 .verbose(verbose)
                 .build()
                 .unwrap();
-                              let opts =
-                                  NatsClientOptions::builder().connect_command(cc)
-                                                              .cluster_uri(cluster_uri.as_str())
-                                                              .build()
-                                                              .unwrap();
-
-                              let publisher = NatsClient::from_options(opts)
-                .map_err(Into::<NatsStreamingError>::into)
-                .and_then(|client| {
-                    NatsStreamingClient::from(client)
-                        .cluster_id(cluster_id)
-                        .connect()
-                })
-                .map_err(|streaming_error| error!("{}", streaming_error))
-                .and_then(move |client| {
-                    sync_tx.send(()).expect("Couldn't synchronize!");
-                    event_rx.for_each(move |event: Vec<u8>| {
-                        let publish_event = client
-                            .publish(HABITAT_SUBJECT.into(), event.into())
-                            .map_err(|e| {
-                                error!("Error publishing event: {:?}", e);
-                            });
-                        executor::spawn(publish_event);
-                        Ok(())
-                    })
-                });
-
-                              ThreadRuntime::new().expect("Couldn't create event stream runtime!")
-                                                  .spawn(publisher)
-                                                  .run()
-                                                  .expect("something seriously wrong has occurred");
-                          })
-                          .expect("Couldn't start events thread!");
+        let opts = NatsClientOptions::builder().connect_command(cc)
+                                                .cluster_uri(cluster_uri.as_str())
+                                                .build()
+                                                .unwrap();
+        let cluster_id = cluster_id.clone();
+        let connected = Rc::new(Cell::new(false));
+        let connected_inner = Rc::clone(&connected);
+        // Only a borrow of `event_rx` and a clone of `in_flight` go into
+        // the future; both are used again once this attempt is over.
+        let event_rx_ref = event_rx.by_ref();
+        let drain_in_flight = Rc::clone(&in_flight);
 
-    sync_rx.recv()?; // TODO (CM): nicer error message
-    Ok(EventStream(event_tx))
+        let connect_and_drain =
+            NatsClient::from_options(opts).map_err(Into::<NatsStreamingError>::into)
+                                          .and_then(|client| {
+                                              NatsStreamingClient::from(client).cluster_id(cluster_id)
+                                                                               .connect()
+                                          })
+                                          .map_err(|streaming_error| {
+                                              error!("Error connecting to NATS Streaming server: {}",
+                                                     streaming_error)
+                                          })
+                                          .and_then(move |client| {
+                                              connected_inner.set(true);
+                                              EVENT_STREAM_STATS.set_connected(true);
+                                              let client = Arc::new(client);
+                                              let (disconnected_tx, disconnected_rx) =
+                                                  oneshot::channel();
+                                              let disconnected_tx: DisconnectSignal =
+                                                  Rc::new(RefCell::new(Some(disconnected_tx)));
+
+                                              let in_flight = drain_in_flight;
+                                              let drain =
+                                                  event_rx_ref.for_each(move |event: QueuedEvent| {
+                                                              spawn_publish(Arc::clone(&client),
+                                                                            event,
+                                                                            delivery_mode,
+                                                                            ack_wait,
+                                                                            max_publish_attempts,
+                                                                            Rc::clone(&in_flight),
+                                                                            Rc::clone(&disconnected_tx));
+                                                              Ok(())
+                                                          });
+
+                                              // Whichever comes first: the
+                                              // buffer's senders all going
+                                              // away (shutdown), or a
+                                              // publish giving up on the
+                                              // connection (live
+                                              // disconnect).
+                                              drain.select2(disconnected_rx.then(|_| Err(())))
+                                                   .map(|_| ())
+                                                   .map_err(|_| ())
+                                          });
+
+        match runtime.block_on(connect_and_drain) {
+            Ok(()) => {
+                // The stream ended because every `EventStream` (and
+                // thus `event_tx`) was dropped; there's nothing left
+                // for us to do.
+                return;
+            }
+            Err(()) => {
+                EVENT_STREAM_STATS.set_connected(false);
+                if connected.get() {
+                    // We made it to a live connection before things
+                    // went wrong, so this is a fresh outage; don't
+                    // penalize it with whatever backoff a previous,
+                    // unrelated outage had climbed to.
+                    backoff = INITIAL_RECONNECT_BACKOFF;
+                }
+                // `connect_and_drain` (and with it, every `by_ref` borrow
+                // of `event_rx` it held) has already been dropped by the
+                // time `block_on` returns, so it's safe to hand the
+                // receiver anything that was still in flight on the
+                // connection we just lost.
+                requeue_in_flight(&in_flight, &event_rx);
+                let sleep_for = jittered_backoff(backoff);
+                warn!("NATS event stream disconnected; reconnecting in {:?}",
+                      sleep_for);
+                thread::sleep(sleep_for);
+                backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Publishes a single event, retrying on a missing/negative ack (up to
+/// `max_attempts`) when `delivery_mode` is `Durable`, instead of
+/// dropping it on the first failure the way `FireAndForget` does.
+///
+/// Giving up on an event (attempts exhausted in `Durable` mode, or the
+/// one and only attempt in `FireAndForget` mode) is our only signal that
+/// the connection itself, not just this one publish, may be dead; it
+/// fires `disconnect` so `run_event_thread` can fall back into the
+/// reconnect path instead of leaving every future publish to fail the
+/// same way. Only the first event to give up on a given connection
+/// actually sends anything; `disconnect` has already been consumed for
+/// every publish after that.
+///
+/// `in_flight` holds the event for as long as it's still eligible for
+/// redelivery: it's inserted before the first attempt and removed once
+/// the event is acked. A `Durable` event that exhausts its attempts is
+/// deliberately left in `in_flight` rather than removed, so a dead
+/// connection migrates it back onto the buffer (see
+/// `requeue_in_flight`) instead of dropping it; `FireAndForget` has no
+/// such delivery guarantee to honor, so it's removed immediately.
+fn spawn_publish(client: Arc<NatsStreamingClient>,
+                  event: QueuedEvent,
+                  delivery_mode: DeliveryMode,
+                  ack_wait: Duration,
+                  max_attempts: u32,
+                  in_flight: InFlight,
+                  disconnect: DisconnectSignal) {
+    let QueuedEvent { id, bytes } = event;
+    in_flight.borrow_mut().insert(id, QueuedEvent { id, bytes: bytes.clone() });
+
+    let publish_attempts = future::loop_fn(1u32, move |attempt| {
+        let client = Arc::clone(&client);
+        let in_flight = Rc::clone(&in_flight);
+        let disconnect = Rc::clone(&disconnect);
+        client.publish(HABITAT_SUBJECT.into(), bytes.clone().into())
+              .timeout(ack_wait)
+              .then(move |result| {
+                  EVENT_STREAM_STATS.record_published();
+                  match result {
+                      Ok(_ack) => {
+                          EVENT_STREAM_STATS.record_acked();
+                          in_flight.borrow_mut().remove(&id);
+                          Ok(Loop::Break(()))
+                      }
+                      Err(e) if delivery_mode == DeliveryMode::Durable
+                                && attempt < max_attempts =>
+                      {
+                          EVENT_STREAM_STATS.record_publish_error();
+                          warn!("Event {} not acked ({:?}); retrying (attempt {}/{})",
+                                id,
+                                e,
+                                attempt + 1,
+                                max_attempts);
+                          Ok(Loop::Continue(attempt + 1))
+                      }
+                      Err(e) => {
+                          EVENT_STREAM_STATS.record_publish_error();
+                          error!("Giving up on event {} after {} attempt(s): {:?}", id, attempt, e);
+                          if delivery_mode == DeliveryMode::FireAndForget {
+                              in_flight.borrow_mut().remove(&id);
+                          }
+                          if let Some(tx) = disconnect.borrow_mut().take() {
+                              let _ = tx.send(());
+                          }
+                          Ok(Loop::Break(()))
+                      }
+                  }
+              })
+    });
+    executor::spawn(publish_attempts);
+}
+
+/// Migrates whatever's still in `in_flight` back onto `event_rx`, in the
+/// order the events were originally queued, so the next connection
+/// attempt redelivers them instead of leaving them to finish retrying
+/// (uselessly) against the connection that just died.
+///
+/// This doesn't cancel the old connection's still-running
+/// `spawn_publish` retries for those same events — they're detached
+/// `executor::spawn` futures tied to a dead `Arc<NatsStreamingClient>`,
+/// and there's no handle left here to abort them. They'll keep retrying
+/// against the dead client until their own `ack_wait`/`max_attempts` run
+/// out, by which point the new connection will typically have already
+/// redelivered the event migrated here. That's fine *because* `Durable`
+/// delivery is NATS Streaming's at-least-once guarantee: a downstream
+/// consumer is expected to dedup by the stable message id (see
+/// `EventMessage::to_bytes`), so a stray duplicate publish from an old,
+/// dying connection is a harmless redundant send, not a correctness bug.
+fn requeue_in_flight(in_flight: &InFlight, event_rx: &EventBufferReceiver<QueuedEvent>) {
+    let mut pending: Vec<QueuedEvent> = in_flight.borrow_mut().drain().map(|(_, event)| event).collect();
+    if pending.is_empty() {
+        return;
+    }
+    pending.sort_by_key(|event| event.id);
+    warn!("Requeuing {} in-flight event(s) after disconnect", pending.len());
+    event_rx.requeue_front(pending);
+}
+
+/// Adds a small amount of random jitter to a backoff duration to avoid a
+/// thundering herd of reconnecting Supervisors.
+fn jittered_backoff(backoff: Duration) -> Duration {
+    let jitter_ms = rand::thread_rng().gen_range(0, RECONNECT_JITTER_MS);
+    backoff + Duration::from_millis(jitter_ms)
 }
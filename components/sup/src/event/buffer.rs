@@ -0,0 +1,335 @@
+// Copyright (c) 2019 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A bounded, in-memory buffer of rendered events awaiting delivery to
+//! the event stream, with a configurable policy for what happens once
+//! it's full.
+//!
+//! Unlike `futures::sync::mpsc`, which only knows how to make a sender
+//! wait for room, this buffer also supports dropping events outright so
+//! a slow or down NATS link can't grow the Supervisor's memory usage
+//! without bound.
+
+use futures::{task,
+              Async,
+              Poll,
+              Stream};
+use std::{collections::VecDeque,
+          sync::{atomic::{AtomicUsize,
+                          Ordering},
+                 Arc,
+                 Condvar,
+                 Mutex}};
+
+/// What to do when `EventBufferSender::send` is called against a buffer
+/// that's already at capacity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the sender until there's room. Guarantees no event is
+    /// lost, at the cost of potentially stalling whoever is publishing
+    /// events.
+    Block,
+    /// Reject the event that's arriving; whatever is already buffered
+    /// is left untouched.
+    DropNewest,
+    /// Evict the oldest buffered event to make room for the one that's
+    /// arriving. Ring-buffer semantics that favor recency, much like a
+    /// watch-style latest-wins channel.
+    DropOldest,
+}
+
+/// What actually happened to an event passed to `EventBufferSender::send`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendOutcome {
+    /// The event was enqueued with room to spare.
+    Enqueued,
+    /// The sender had to wait for room, but the event was eventually
+    /// enqueued (only possible under `OverflowPolicy::Block`).
+    Blocked,
+    /// The incoming event was dropped because the buffer was full
+    /// (`OverflowPolicy::DropNewest`).
+    DroppedNewest,
+    /// The oldest buffered event was evicted to make room for this one
+    /// (`OverflowPolicy::DropOldest`).
+    DroppedOldest,
+}
+
+struct Shared<T> {
+    queue:        Mutex<VecDeque<T>>,
+    capacity:     usize,
+    policy:       OverflowPolicy,
+    not_full:     Condvar,
+    waker:        Mutex<Option<task::Task>>,
+    sender_count: AtomicUsize,
+    /// Called with the queue's length after every mutation (enqueue,
+    /// dequeue, or requeue), so a caller that wants to report queue
+    /// depth (e.g. as a metrics gauge) doesn't have to re-derive it from
+    /// scattered call sites and risk missing one.
+    depth_hook:   Box<dyn Fn(usize) + Send + Sync>,
+}
+
+impl<T> Shared<T> {
+    fn report_depth(&self, queue: &VecDeque<T>) { (self.depth_hook)(queue.len()); }
+}
+
+/// The producer half of a bounded event buffer. Cheap to clone; every
+/// clone feeds the same underlying queue.
+pub struct EventBufferSender<T>(Arc<Shared<T>>);
+
+impl<T> Clone for EventBufferSender<T> {
+    fn clone(&self) -> Self {
+        self.0.sender_count.fetch_add(1, Ordering::SeqCst);
+        EventBufferSender(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Drop for EventBufferSender<T> {
+    fn drop(&mut self) {
+        if self.0.sender_count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // We were the last sender; wake the receiver so it can
+            // notice the queue is now orphaned and shut down.
+            if let Some(task) = self.0.waker.lock().expect("event buffer waker lock poisoned")
+                                    .take()
+            {
+                task.notify();
+            }
+        }
+    }
+}
+
+/// The consumer half of a bounded event buffer. Implements `Stream` so
+/// it can be drained the same way a `futures::sync::mpsc::Receiver` is.
+pub struct EventBufferReceiver<T>(Arc<Shared<T>>);
+
+/// Creates a new bounded event buffer with room for `capacity` items,
+/// applying `policy` once it fills up.
+pub fn bounded<T>(capacity: usize, policy: OverflowPolicy) -> (EventBufferSender<T>, EventBufferReceiver<T>) {
+    bounded_with_depth_hook(capacity, policy, |_depth| {})
+}
+
+/// Like `bounded`, but also calls `depth_hook` with the queue's length
+/// after every enqueue, dequeue, and requeue, so callers can keep a
+/// depth gauge live without having to re-sample it from outside (and
+/// without missing a call site as the buffer grows new ways to mutate
+/// the queue).
+pub fn bounded_with_depth_hook<T>(
+    capacity: usize,
+    policy: OverflowPolicy,
+    depth_hook: impl Fn(usize) + Send + Sync + 'static)
+    -> (EventBufferSender<T>, EventBufferReceiver<T>) {
+    let shared = Arc::new(Shared { queue:        Mutex::new(VecDeque::with_capacity(capacity)),
+                                   capacity:     capacity.max(1),
+                                   policy,
+                                   not_full:     Condvar::new(),
+                                   waker:        Mutex::new(None),
+                                   sender_count: AtomicUsize::new(1),
+                                   depth_hook:   Box::new(depth_hook), });
+    (EventBufferSender(Arc::clone(&shared)), EventBufferReceiver(shared))
+}
+
+impl<T> EventBufferSender<T> {
+    /// Queues an item, applying the buffer's overflow policy if it's
+    /// already full. Returns what actually happened, so callers (and
+    /// metrics) can observe loss.
+    pub fn send(&self, event: T) -> SendOutcome {
+        let mut queue = self.0.queue.lock().expect("event buffer lock poisoned");
+        let outcome = if queue.len() < self.0.capacity {
+            queue.push_back(event);
+            SendOutcome::Enqueued
+        } else {
+            match self.0.policy {
+                OverflowPolicy::DropNewest => SendOutcome::DroppedNewest,
+                OverflowPolicy::DropOldest => {
+                    queue.pop_front();
+                    queue.push_back(event);
+                    SendOutcome::DroppedOldest
+                }
+                OverflowPolicy::Block => {
+                    while queue.len() >= self.0.capacity {
+                        queue = self.0
+                                    .not_full
+                                    .wait(queue)
+                                    .expect("event buffer condvar poisoned");
+                    }
+                    queue.push_back(event);
+                    SendOutcome::Blocked
+                }
+            }
+        };
+        self.0.report_depth(&queue);
+        drop(queue);
+        self.wake_receiver();
+        outcome
+    }
+
+    /// Cheaply reports whether a call to `send` right now would be
+    /// dropped outright, without requiring the caller to have built
+    /// the event it would have sent.
+    pub fn would_drop(&self) -> bool {
+        self.0.policy == OverflowPolicy::DropNewest
+        && self.0.queue.lock().expect("event buffer lock poisoned").len() >= self.0.capacity
+    }
+
+    fn wake_receiver(&self) {
+        if let Some(task) = self.0.waker.lock().expect("event buffer waker lock poisoned").take() {
+            task.notify();
+        }
+    }
+}
+
+impl<T> EventBufferReceiver<T> {
+    /// Pushes previously-dequeued events back onto the front of the
+    /// queue, in the order given, so they're the next ones redelivered
+    /// instead of being lost. Meant for migrating events that were
+    /// in-flight on a connection that's gone away; unlike `send`, this
+    /// deliberately ignores `capacity` and the overflow policy; losing
+    /// events we'd already accepted would defeat the purpose of
+    /// redelivering them.
+    ///
+    /// Takes `&self` rather than `&mut self`: this only touches the
+    /// shared queue, not the `Stream` impl's polling state, so it can be
+    /// called while the receiver is otherwise idle between connections.
+    pub fn requeue_front(&self, events: Vec<T>) {
+        let mut queue = self.0.queue.lock().expect("event buffer lock poisoned");
+        for event in events.into_iter().rev() {
+            queue.push_front(event);
+        }
+        self.0.report_depth(&queue);
+    }
+}
+
+impl<T> Stream for EventBufferReceiver<T> {
+    type Error = ();
+    type Item = T;
+
+    fn poll(&mut self) -> Poll<Option<T>, ()> {
+        let mut queue = self.0.queue.lock().expect("event buffer lock poisoned");
+        if let Some(event) = queue.pop_front() {
+            self.0.report_depth(&queue);
+            self.0.not_full.notify_one();
+            return Ok(Async::Ready(Some(event)));
+        }
+        if self.0.sender_count.load(Ordering::SeqCst) == 0 {
+            return Ok(Async::Ready(None));
+        }
+        *self.0.waker.lock().expect("event buffer waker lock poisoned") = Some(task::current());
+        Ok(Async::NotReady)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::{self,
+                             Notify},
+                  Async};
+    use std::{sync::{atomic::AtomicBool,
+                     mpsc,
+                     Barrier},
+              thread,
+              time::Duration};
+
+    struct FlagNotify(Arc<AtomicBool>);
+
+    impl Notify for FlagNotify {
+        fn notify(&self, _id: usize) { self.0.store(true, Ordering::SeqCst); }
+    }
+
+    #[test]
+    fn drop_oldest_evicts_in_fifo_order() {
+        let (tx, rx) = bounded(2, OverflowPolicy::DropOldest);
+        assert_eq!(tx.send(1), SendOutcome::Enqueued);
+        assert_eq!(tx.send(2), SendOutcome::Enqueued);
+        // Buffer is full; enqueuing 3 should evict 1, not 2.
+        assert_eq!(tx.send(3), SendOutcome::DroppedOldest);
+
+        let mut rx = rx;
+        assert_eq!(rx.poll(), Ok(Async::Ready(Some(2))));
+        assert_eq!(rx.poll(), Ok(Async::Ready(Some(3))));
+    }
+
+    #[test]
+    fn drop_newest_rejects_at_capacity() {
+        let (tx, rx) = bounded(1, OverflowPolicy::DropNewest);
+        assert_eq!(tx.send(1), SendOutcome::Enqueued);
+        assert!(tx.would_drop());
+        assert_eq!(tx.send(2), SendOutcome::DroppedNewest);
+
+        let mut rx = rx;
+        assert_eq!(rx.poll(), Ok(Async::Ready(Some(1))));
+    }
+
+    #[test]
+    fn block_waits_for_room_then_succeeds() {
+        let (tx, rx) = bounded(1, OverflowPolicy::Block);
+        assert_eq!(tx.send(1), SendOutcome::Enqueued);
+
+        let barrier = Arc::new(Barrier::new(2));
+        let sender_barrier = Arc::clone(&barrier);
+        let (result_tx, result_rx) = mpsc::channel();
+        let blocked_send = thread::spawn(move || {
+            sender_barrier.wait();
+            // This blocks until the item below is popped off the queue.
+            result_tx.send(tx.send(2)).unwrap();
+        });
+
+        barrier.wait();
+        // Give the spawned thread a moment to actually reach the
+        // condvar wait before we free up a slot for it.
+        thread::sleep(Duration::from_millis(50));
+        assert!(result_rx.try_recv().is_err(), "send should still be blocked");
+
+        let mut rx = rx;
+        assert_eq!(rx.poll(), Ok(Async::Ready(Some(1))));
+        assert_eq!(result_rx.recv().unwrap(), SendOutcome::Blocked);
+        blocked_send.join().unwrap();
+    }
+
+    #[test]
+    fn stream_wakes_on_push_after_not_ready() {
+        let (tx, rx) = bounded(4, OverflowPolicy::DropOldest);
+        let notified = Arc::new(AtomicBool::new(false));
+        let notify = Arc::new(FlagNotify(Arc::clone(&notified)));
+        let mut spawned = executor::spawn(rx);
+
+        // Nothing queued yet: the poll should park the current task
+        // (via `task::current()`) rather than busy-loop.
+        assert_eq!(spawned.poll_stream_notify(&notify, 0), Ok(Async::NotReady));
+        assert!(!notified.load(Ordering::SeqCst));
+
+        tx.send(42);
+        assert!(notified.load(Ordering::SeqCst), "push should wake the parked task");
+        assert_eq!(spawned.poll_stream_notify(&notify, 0), Ok(Async::Ready(Some(42))));
+    }
+
+    #[test]
+    fn depth_hook_fires_on_enqueue_dequeue_and_requeue() {
+        let depths = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&depths);
+        let (tx, mut rx) =
+            bounded_with_depth_hook(4, OverflowPolicy::DropOldest, move |depth| {
+                recorded.lock().unwrap().push(depth);
+            });
+
+        tx.send(1);
+        tx.send(2);
+        assert_eq!(*depths.lock().unwrap(), vec![1, 2]);
+
+        assert_eq!(rx.poll(), Ok(Async::Ready(Some(1))));
+        assert_eq!(*depths.lock().unwrap(), vec![1, 2, 1]);
+
+        rx.requeue_front(vec![1]);
+        assert_eq!(*depths.lock().unwrap(), vec![1, 2, 1, 2]);
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2019 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A small metrics surface for the event stream, so operators can tell
+//! whether telemetry is actually flowing instead of silently piling up
+//! or vanishing behind a dead NATS link.
+
+use std::sync::atomic::{AtomicBool,
+                        AtomicU64,
+                        Ordering};
+
+/// Counters and gauges for the event stream. Lives behind the `event`
+/// module's global state; see `event::stats`.
+#[derive(Default)]
+pub struct EventStreamStats {
+    enqueued:       AtomicU64,
+    dropped:        AtomicU64,
+    published:      AtomicU64,
+    acked:          AtomicU64,
+    publish_errors: AtomicU64,
+    queue_depth:    AtomicU64,
+    connected:      AtomicBool,
+}
+
+impl EventStreamStats {
+    pub fn record_enqueued(&self) { self.enqueued.fetch_add(1, Ordering::Relaxed); }
+
+    pub fn record_dropped(&self) { self.dropped.fetch_add(1, Ordering::Relaxed); }
+
+    pub fn record_published(&self) { self.published.fetch_add(1, Ordering::Relaxed); }
+
+    pub fn record_acked(&self) { self.acked.fetch_add(1, Ordering::Relaxed); }
+
+    pub fn record_publish_error(&self) { self.publish_errors.fetch_add(1, Ordering::Relaxed); }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.store(depth as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_connected(&self, connected: bool) { self.connected.store(connected, Ordering::Relaxed); }
+
+    /// Takes a point-in-time copy of these stats, suitable for
+    /// rendering.
+    pub fn snapshot(&self) -> EventStreamStatsSnapshot {
+        EventStreamStatsSnapshot { enqueued:       self.enqueued.load(Ordering::Relaxed),
+                                   dropped:        self.dropped.load(Ordering::Relaxed),
+                                   published:      self.published.load(Ordering::Relaxed),
+                                   acked:          self.acked.load(Ordering::Relaxed),
+                                   publish_errors: self.publish_errors.load(Ordering::Relaxed),
+                                   queue_depth:    self.queue_depth.load(Ordering::Relaxed),
+                                   connected:      self.connected.load(Ordering::Relaxed), }
+    }
+}
+
+/// A point-in-time copy of `EventStreamStats`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EventStreamStatsSnapshot {
+    pub enqueued:       u64,
+    pub dropped:        u64,
+    pub published:      u64,
+    pub acked:          u64,
+    pub publish_errors: u64,
+    pub queue_depth:    u64,
+    pub connected:      bool,
+}
+
+impl EventStreamStatsSnapshot {
+    /// Renders these stats in Prometheus text-exposition format, for
+    /// the Supervisor's HTTP gateway to serve alongside its other
+    /// metrics.
+    pub fn to_prometheus(&self) -> String {
+        format!("# TYPE habitat_event_stream_enqueued_total counter\n\
+                 habitat_event_stream_enqueued_total {}\n\
+                 # TYPE habitat_event_stream_dropped_total counter\n\
+                 habitat_event_stream_dropped_total {}\n\
+                 # TYPE habitat_event_stream_published_total counter\n\
+                 habitat_event_stream_published_total {}\n\
+                 # TYPE habitat_event_stream_acked_total counter\n\
+                 habitat_event_stream_acked_total {}\n\
+                 # TYPE habitat_event_stream_publish_errors_total counter\n\
+                 habitat_event_stream_publish_errors_total {}\n\
+                 # TYPE habitat_event_stream_queue_depth gauge\n\
+                 habitat_event_stream_queue_depth {}\n\
+                 # TYPE habitat_event_stream_connected gauge\n\
+                 habitat_event_stream_connected {}\n",
+                self.enqueued,
+                self.dropped,
+                self.published,
+                self.acked,
+                self.publish_errors,
+                self.queue_depth,
+                if self.connected { 1 } else { 0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_start_at_zero_and_connected_starts_false() {
+        let stats = EventStreamStats::default();
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.enqueued, 0);
+        assert_eq!(snapshot.dropped, 0);
+        assert_eq!(snapshot.published, 0);
+        assert_eq!(snapshot.acked, 0);
+        assert_eq!(snapshot.publish_errors, 0);
+        assert_eq!(snapshot.queue_depth, 0);
+        assert!(!snapshot.connected);
+    }
+
+    #[test]
+    fn record_methods_increment_their_own_counter_only() {
+        let stats = EventStreamStats::default();
+        stats.record_enqueued();
+        stats.record_enqueued();
+        stats.record_dropped();
+        stats.record_published();
+        stats.record_acked();
+        stats.record_publish_error();
+        stats.set_queue_depth(7);
+        stats.set_connected(true);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.enqueued, 2);
+        assert_eq!(snapshot.dropped, 1);
+        assert_eq!(snapshot.published, 1);
+        assert_eq!(snapshot.acked, 1);
+        assert_eq!(snapshot.publish_errors, 1);
+        assert_eq!(snapshot.queue_depth, 7);
+        assert!(snapshot.connected);
+    }
+
+    #[test]
+    fn to_prometheus_renders_counters_and_gauges_with_correct_types() {
+        let snapshot = EventStreamStatsSnapshot { enqueued:       3,
+                                                   dropped:        2,
+                                                   published:      1,
+                                                   acked:          1,
+                                                   publish_errors: 4,
+                                                   queue_depth:    9,
+                                                   connected:      true, };
+        let rendered = snapshot.to_prometheus();
+
+        assert!(rendered.contains("# TYPE habitat_event_stream_enqueued_total counter\n\
+                                    habitat_event_stream_enqueued_total 3\n"));
+        assert!(rendered.contains("# TYPE habitat_event_stream_dropped_total counter\n\
+                                    habitat_event_stream_dropped_total 2\n"));
+        assert!(rendered.contains("# TYPE habitat_event_stream_published_total counter\n\
+                                    habitat_event_stream_published_total 1\n"));
+        assert!(rendered.contains("# TYPE habitat_event_stream_acked_total counter\n\
+                                    habitat_event_stream_acked_total 1\n"));
+        assert!(rendered.contains("# TYPE habitat_event_stream_publish_errors_total counter\n\
+                                    habitat_event_stream_publish_errors_total 4\n"));
+        assert!(rendered.contains("# TYPE habitat_event_stream_queue_depth gauge\n\
+                                    habitat_event_stream_queue_depth 9\n"));
+        assert!(rendered.contains("# TYPE habitat_event_stream_connected gauge\n\
+                                    habitat_event_stream_connected 1\n"));
+    }
+
+    #[test]
+    fn to_prometheus_maps_disconnected_to_zero() {
+        let snapshot = EventStreamStatsSnapshot { connected: false,
+                                                    ..Default::default() };
+        assert!(snapshot.to_prometheus()
+                         .contains("habitat_event_stream_connected 0\n"));
+    }
+}
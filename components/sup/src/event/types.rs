@@ -0,0 +1,137 @@
+// Copyright (c) 2019 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The events the Supervisor knows how to publish, along with the
+//! metadata that gets merged into each one right before it's rendered
+//! and sent out over the stream.
+
+use crate::error::Result;
+use chrono::{DateTime,
+             Utc};
+use serde::Serialize;
+
+/// Metadata about the Supervisor that sent an event. Merged into every
+/// event just before it's published; see `EventCore`.
+#[derive(Clone, Debug, Serialize)]
+pub struct SupervisorMetadata {
+    pub supervisor_id: String,
+}
+
+/// Metadata about the service a service-scoped event pertains to.
+#[derive(Clone, Debug, Serialize)]
+pub struct ServiceMetadata {
+    pub service_group: String,
+}
+
+/// Common interface implemented by every event type that can be sent out
+/// over the event stream.
+pub trait EventMessage {
+    /// Attach the Supervisor-wide metadata that's common to every event.
+    fn supervisor_metadata(&mut self, supervisor_metadata: Option<SupervisorMetadata>);
+
+    /// Render this event to the bytes that get put on the wire.
+    ///
+    /// `message_id` and `generated_at` are stamped onto the envelope
+    /// rather than the event itself, so a durable consumer on the
+    /// other end can deduplicate replays after a reconnect.
+    fn to_bytes(&self, message_id: &str, generated_at: DateTime<Utc>) -> Result<Vec<u8>>;
+}
+
+/// Wraps a rendered event with the delivery metadata every event
+/// carries, regardless of its specific payload.
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    message_id:   &'a str,
+    generated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    payload:      &'a T,
+}
+
+/// Shared by every `EventMessage` impl's `to_bytes`.
+fn render<T: Serialize>(payload: &T, message_id: &str, generated_at: DateTime<Utc>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&Envelope { message_id,
+                                      generated_at,
+                                      payload })?)
+}
+
+/// Sent when a service is started.
+#[derive(Clone, Debug, Serialize)]
+pub struct ServiceStartedEvent {
+    pub service_metadata:    Option<ServiceMetadata>,
+    pub supervisor_metadata: Option<SupervisorMetadata>,
+}
+
+impl EventMessage for ServiceStartedEvent {
+    fn supervisor_metadata(&mut self, supervisor_metadata: Option<SupervisorMetadata>) {
+        self.supervisor_metadata = supervisor_metadata;
+    }
+
+    fn to_bytes(&self, message_id: &str, generated_at: DateTime<Utc>) -> Result<Vec<u8>> {
+        render(self, message_id, generated_at)
+    }
+}
+
+/// Sent when a service is stopped.
+#[derive(Clone, Debug, Serialize)]
+pub struct ServiceStoppedEvent {
+    pub service_metadata:    Option<ServiceMetadata>,
+    pub supervisor_metadata: Option<SupervisorMetadata>,
+}
+
+impl EventMessage for ServiceStoppedEvent {
+    fn supervisor_metadata(&mut self, supervisor_metadata: Option<SupervisorMetadata>) {
+        self.supervisor_metadata = supervisor_metadata;
+    }
+
+    fn to_bytes(&self, message_id: &str, generated_at: DateTime<Utc>) -> Result<Vec<u8>> {
+        render(self, message_id, generated_at)
+    }
+}
+
+/// Sent when the gossip subsystem confirms that a member has gone
+/// silent for longer than the suspicion timeout (`Suspect` ->
+/// `Confirmed`).
+#[derive(Clone, Debug, Serialize)]
+pub struct MemberConfirmedEvent {
+    pub member_id:           String,
+    pub supervisor_metadata: Option<SupervisorMetadata>,
+}
+
+impl EventMessage for MemberConfirmedEvent {
+    fn supervisor_metadata(&mut self, supervisor_metadata: Option<SupervisorMetadata>) {
+        self.supervisor_metadata = supervisor_metadata;
+    }
+
+    fn to_bytes(&self, message_id: &str, generated_at: DateTime<Utc>) -> Result<Vec<u8>> {
+        render(self, message_id, generated_at)
+    }
+}
+
+/// Sent when the gossip subsystem gives up on a member entirely
+/// (`Confirmed` -> `Departed`).
+#[derive(Clone, Debug, Serialize)]
+pub struct MemberDepartedEvent {
+    pub member_id:           String,
+    pub supervisor_metadata: Option<SupervisorMetadata>,
+}
+
+impl EventMessage for MemberDepartedEvent {
+    fn supervisor_metadata(&mut self, supervisor_metadata: Option<SupervisorMetadata>) {
+        self.supervisor_metadata = supervisor_metadata;
+    }
+
+    fn to_bytes(&self, message_id: &str, generated_at: DateTime<Utc>) -> Result<Vec<u8>> {
+        render(self, message_id, generated_at)
+    }
+}